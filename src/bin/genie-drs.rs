@@ -0,0 +1,146 @@
+//! A command-line tool for inspecting, extracting, and creating DRS archives.
+
+extern crate clap;
+extern crate genie_drs;
+extern crate indicatif;
+
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use clap::{App, Arg, SubCommand};
+use genie_drs::{DRSWriter, DRS};
+use indicatif::{ProgressBar, ProgressStyle};
+
+fn main() {
+    let matches = App::new("genie-drs")
+        .about("List, extract, and pack Genie Engine .drs archives")
+        .subcommand(SubCommand::with_name("list")
+            .about("List the tables and resources in a DRS archive")
+            .arg(Arg::with_name("archive").required(true)))
+        .subcommand(SubCommand::with_name("extract")
+            .about("Extract every resource in a DRS archive into a directory")
+            .arg(Arg::with_name("archive").required(true))
+            .arg(Arg::with_name("outdir").required(true)))
+        .subcommand(SubCommand::with_name("pack")
+            .about("Pack a directory of resources into a DRS archive")
+            .arg(Arg::with_name("indir").required(true))
+            .arg(Arg::with_name("archive").required(true)))
+        .get_matches();
+
+    let result = match matches.subcommand() {
+        ("list", Some(sub)) => list(sub.value_of("archive").unwrap()),
+        ("extract", Some(sub)) =>
+            extract(sub.value_of("archive").unwrap(), sub.value_of("outdir").unwrap()),
+        ("pack", Some(sub)) =>
+            pack(sub.value_of("indir").unwrap(), sub.value_of("archive").unwrap()),
+        _ => {
+            eprintln!("{}", matches.usage());
+            std::process::exit(1);
+        },
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Format a resource type as the string it's displayed as (reversed, like the `Debug` impl for
+/// `DRSTable`, with trailing spaces trimmed).
+fn type_name(resource_type: [u8; 4]) -> String {
+    let mut reversed = resource_type;
+    reversed.reverse();
+    String::from_utf8_lossy(&reversed).trim_end().to_string()
+}
+
+/// Parse a `name.ext` file name back into a resource type and id, as written by `extract`.
+fn parse_file_name(file_name: &str) -> Option<([u8; 4], u32)> {
+    let (id, ext) = file_name.split_once('.')?;
+    let id = id.parse().ok()?;
+
+    let mut resource_type = [b' '; 4];
+    let ext_bytes = ext.as_bytes();
+    let len = ext_bytes.len().min(4);
+    resource_type[..len].copy_from_slice(&ext_bytes[..len]);
+    resource_type.reverse();
+
+    Some((resource_type, id))
+}
+
+fn list(archive_path: &str) -> io::Result<()> {
+    let file = File::open(archive_path)?;
+    let drs = DRS::new(file)?;
+
+    for table in drs.tables() {
+        println!("{}", type_name(table.resource_type));
+        for resource in table.resources() {
+            println!("  {:>10}  {} bytes", resource.id, resource.size);
+        }
+    }
+
+    Ok(())
+}
+
+fn extract(archive_path: &str, outdir: &str) -> io::Result<()> {
+    let file = File::open(archive_path)?;
+    let mut drs = DRS::new(file)?;
+    fs::create_dir_all(outdir)?;
+
+    let num_resources: u64 = drs.tables().map(|table| u64::from(table.resources().count() as u32)).sum();
+    let progress = ProgressBar::new(num_resources);
+    progress.set_style(ProgressStyle::default_bar()
+        .template("{bar:40} {pos}/{len} {msg}")
+        .unwrap());
+
+    let resources: Vec<([u8; 4], u32)> = drs.tables()
+        .flat_map(|table| table.resources().map(move |resource| (table.resource_type, resource.id)))
+        .collect();
+
+    for (resource_type, id) in resources {
+        let file_name = format!("{}.{}", id, type_name(resource_type));
+        progress.set_message(file_name.clone());
+
+        let mut reader = drs.open_resource(resource_type, id)?;
+        let mut out = File::create(Path::new(outdir).join(file_name))?;
+        io::copy(&mut reader, &mut out)?;
+
+        progress.inc(1);
+    }
+
+    progress.finish();
+    Ok(())
+}
+
+fn pack(indir: &str, archive_path: &str) -> io::Result<()> {
+    let entries: Vec<_> = fs::read_dir(indir)?.collect::<Result<_, _>>()?;
+
+    let progress = ProgressBar::new(entries.len() as u64);
+    progress.set_style(ProgressStyle::default_bar()
+        .template("{bar:40} {pos}/{len} {msg}")
+        .unwrap());
+
+    let out = File::create(archive_path)?;
+    let mut writer = DRSWriter::new(out);
+
+    for entry in entries {
+        let path = entry.path();
+        let file_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        let (resource_type, id) = match parse_file_name(file_name) {
+            Some(parsed) => parsed,
+            None => continue,
+        };
+
+        progress.set_message(file_name.to_string());
+        let data = fs::read(&path)?;
+        writer.add_resource(resource_type, id, &data);
+        progress.inc(1);
+    }
+
+    progress.finish();
+    writer.write()
+}
@@ -22,10 +22,11 @@
 
 extern crate byteorder;
 
-use std::io::{Read, Seek, SeekFrom, Error, ErrorKind};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write, Error, ErrorKind};
 use std::str;
 use std::slice;
-use byteorder::{ReadBytesExt, LE};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 
 /// The DRS archive header.
 pub struct DRSHeader {
@@ -78,7 +79,7 @@ impl std::fmt::Debug for DRSHeader {
 /// A table containing resource entries.
 pub struct DRSTable {
     /// Type of the resource as a little-endian char array.
-    resource_type: [u8; 4],
+    pub resource_type: [u8; 4],
     /// Offset in the DRS archive where this table's resource entries can be found.
     offset: u32,
     /// Number of resource entries in this table.
@@ -110,17 +111,12 @@ impl DRSTable {
         Ok(())
     }
 
-    fn resources(&self) -> DRSResourceIterator {
+    pub fn resources(&self) -> DRSResourceIterator {
         self.resources.iter()
     }
-    fn resources_mut(&mut self) -> DRSResourceIteratorMut {
+    pub fn resources_mut(&mut self) -> DRSResourceIteratorMut {
         self.resources.iter_mut()
     }
-
-    fn get_resource(&self, id: u32) -> Result<&DRSResource, Error> {
-        self.resources().find(|resource| { resource.id == id })
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Resource does not exist"))
-    }
 }
 
 impl std::fmt::Debug for DRSTable {
@@ -141,11 +137,11 @@ impl std::fmt::Debug for DRSTable {
 #[derive(Debug)]
 pub struct DRSResource {
     /// The resource ID.
-    id: u32,
+    pub id: u32,
     /// The offset into the DRS archive where the resource can be found.
-    offset: u32,
+    pub offset: u32,
     /// The size in bytes of the resource.
-    size: u32,
+    pub size: u32,
 }
 
 impl DRSResource {
@@ -162,17 +158,101 @@ impl DRSResource {
     }
 }
 
+/// A streaming, size-clamped view over a single resource in a DRS archive.
+///
+/// `Read` and `Seek` are relative to the resource rather than the underlying handle: EOF is
+/// reported at the resource boundary instead of the end of the archive, and `SeekFrom::End` is
+/// relative to the resource's size.
+pub struct ResourceReader<R: Read + Seek> {
+    handle: R,
+    /// Absolute offset of the resource's first byte in the underlying handle.
+    offset: u64,
+    /// Size of the resource in bytes.
+    size: u64,
+    /// Current position within the resource, relative to `offset`.
+    pos: u64,
+    /// Whether `handle` is currently positioned at `offset + pos`.
+    in_place: bool,
+}
+
+impl<R: Read + Seek> ResourceReader<R> {
+    fn new(handle: R, offset: u64, size: u64) -> ResourceReader<R> {
+        ResourceReader {
+            handle,
+            offset,
+            size,
+            pos: 0,
+            in_place: false,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for ResourceReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if !self.in_place {
+            self.handle.seek(SeekFrom::Start(self.offset + self.pos))?;
+            self.in_place = true;
+        }
+
+        let remaining = self.size.saturating_sub(self.pos) as usize;
+        let len = std::cmp::min(buf.len(), remaining);
+        let n = self.handle.read(&mut buf[..len])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for ResourceReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::End(n) => self.size as i64 + n,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+        };
+
+        if new_pos < 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid seek to a negative position"));
+        }
+
+        self.pos = new_pos as u64;
+        self.in_place = false;
+        Ok(self.pos)
+    }
+}
+
 pub type DRSTableIterator<'a> = slice::Iter<'a, DRSTable>;
 pub type DRSTableIteratorMut<'a> = slice::IterMut<'a, DRSTable>;
 pub type DRSResourceIterator<'a> = slice::Iter<'a, DRSResource>;
 pub type DRSResourceIteratorMut<'a> = slice::IterMut<'a, DRSResource>;
 
+/// A problem found while validating a DRS archive with `DRS::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The archive's version field was not `"1.00"`.
+    BadVersion([u8; 4]),
+    /// The file is shorter than the archive's own directory claims it to be.
+    TruncatedFile { expected: u64, actual: u64 },
+    /// A table's resource entries start before the end of the table headers or extend past the
+    /// end of the directory.
+    TableOutOfBounds { resource_type: [u8; 4], offset: u32 },
+    /// A resource's byte range extends past the end of the file.
+    ResourceOutOfBounds { resource_type: [u8; 4], id: u32, offset: u32, size: u32 },
+    /// Two resources' byte ranges overlap.
+    OverlappingResources { a: ([u8; 4], u32), b: ([u8; 4], u32) },
+}
+
 /// A DRS archive.
 #[derive(Debug)]
 pub struct DRS<R: Read + Seek> {
     handle: R,
     header: Option<DRSHeader>,
     tables: Vec<DRSTable>,
+    /// Maps a resource type to its index in `tables`.
+    table_indices: HashMap<[u8; 4], usize>,
+    /// Maps a (resource type, resource id) pair to the resource's index within its table.
+    resource_indices: HashMap<([u8; 4], u32), usize>,
+    /// Maps a resource id to the resource type of the table it lives in.
+    resource_types: HashMap<u32, [u8; 4]>,
 }
 
 impl<R: Read + Seek> DRS<R> {
@@ -183,6 +263,9 @@ impl<R: Read + Seek> DRS<R> {
             handle,
             header: None,
             tables: vec![],
+            table_indices: HashMap::new(),
+            resource_indices: HashMap::new(),
+            resource_types: HashMap::new(),
         };
         drs.read_header()?;
         drs.read_tables()?;
@@ -210,31 +293,113 @@ impl<R: Read + Seek> DRS<R> {
         Ok(())
     }
 
-    /// Read the list of resources.
+    /// Read the list of resources, and build the lookup indices used by the `get_*` methods.
     fn read_dictionary(&mut self) -> Result<(), Error> {
-        for table in &mut self.tables {
+        for (table_index, table) in self.tables.iter_mut().enumerate() {
             table.read_resources(&mut self.handle)?;
+
+            self.table_indices.insert(table.resource_type, table_index);
+            for (resource_index, resource) in table.resources().enumerate() {
+                self.resource_indices.insert((table.resource_type, resource.id), resource_index);
+                // Keep the first table that claims this id, matching the original linear-scan
+                // behavior where `tables.iter().find(...)` stopped at the first match.
+                self.resource_types.entry(resource.id).or_insert(table.resource_type);
+            }
         }
         Ok(())
     }
 
     pub fn get_table_mut(&mut self, resource_type: [u8; 4]) -> Result<&mut DRSTable, Error> {
-        self.tables.iter_mut().find(|table| { table.resource_type == resource_type })
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Resource type does not exist"))
+        let index = *self.table_indices.get(&resource_type)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Resource type does not exist"))?;
+        Ok(&mut self.tables[index])
     }
 
     pub fn get_table(&self, resource_type: [u8; 4]) -> Result<&DRSTable, Error> {
-        self.tables.iter().find(|table| { table.resource_type == resource_type })
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Resource type does not exist"))
+        let index = *self.table_indices.get(&resource_type)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Resource type does not exist"))?;
+        Ok(&self.tables[index])
     }
 
     pub fn get_resource(&self, resource_type: [u8; 4], id: u32) -> Result<&DRSResource, Error> {
-        self.get_table(resource_type)?.get_resource(id)
+        let table_index = *self.table_indices.get(&resource_type)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Resource type does not exist"))?;
+        let resource_index = *self.resource_indices.get(&(resource_type, id))
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Resource does not exist"))?;
+        Ok(&self.tables[table_index].resources[resource_index])
     }
 
+    /// Look up which table a resource id lives in. If the same id appears in more than one
+    /// table, the table that comes first in `tables()` wins.
     pub fn get_resource_type(&self, id: u32) -> Option<[u8; 4]> {
-        self.tables.iter().find(|table| table.get_resource(id).is_ok())
-            .map(|table| table.resource_type)
+        self.resource_types.get(&id).cloned()
+    }
+
+    /// Check that this archive is well-formed, instead of trusting its directory blindly.
+    ///
+    /// Returns every problem found (bad version, out-of-bounds or overlapping resources,
+    /// truncated file) rather than stopping at the first one, so callers can report all issues
+    /// at once.
+    pub fn validate(&mut self) -> Result<Vec<ValidationError>, Error> {
+        let mut errors = vec![];
+
+        let header = self.header.as_ref().expect("header must be read before validating");
+        if &header.version != b"1.00" {
+            errors.push(ValidationError::BadVersion(header.version));
+        }
+
+        let actual_len = self.handle.seek(SeekFrom::End(0))?;
+        if actual_len < u64::from(header.directory_size) {
+            errors.push(ValidationError::TruncatedFile {
+                expected: u64::from(header.directory_size),
+                actual: actual_len,
+            });
+        }
+
+        let entry_tables_start =
+            u64::from(HEADER_SIZE) + u64::from(header.num_resource_types) * u64::from(TABLE_HEADER_SIZE);
+        for table in &self.tables {
+            let table_start = u64::from(table.offset);
+            let table_end = table_start + u64::from(table.num_resources) * u64::from(RESOURCE_ENTRY_SIZE);
+            if table_start < entry_tables_start || table_end > u64::from(header.directory_size) {
+                errors.push(ValidationError::TableOutOfBounds {
+                    resource_type: table.resource_type,
+                    offset: table.offset,
+                });
+            }
+        }
+
+        let mut ranges = vec![];
+        for table in &self.tables {
+            for resource in table.resources() {
+                let start = u64::from(resource.offset);
+                let end = start + u64::from(resource.size);
+                if end > actual_len {
+                    errors.push(ValidationError::ResourceOutOfBounds {
+                        resource_type: table.resource_type,
+                        id: resource.id,
+                        offset: resource.offset,
+                        size: resource.size,
+                    });
+                }
+                ranges.push((table.resource_type, resource.id, start, end));
+            }
+        }
+
+        for i in 0..ranges.len() {
+            for j in (i + 1)..ranges.len() {
+                let (type_a, id_a, start_a, end_a) = ranges[i];
+                let (type_b, id_b, start_b, end_b) = ranges[j];
+                if start_a < end_b && start_b < end_a {
+                    errors.push(ValidationError::OverlappingResources {
+                        a: (type_a, id_a),
+                        b: (type_b, id_b),
+                    });
+                }
+            }
+        }
+
+        Ok(errors)
     }
 
     /// Read a file from the DRS archive.
@@ -249,6 +414,13 @@ impl<R: Read + Seek> DRS<R> {
         Ok(buf.into_boxed_slice())
     }
 
+    /// Open a streaming, `Read + Seek` view over a single resource, without reading it into
+    /// memory up front.
+    pub fn open_resource(&mut self, resource_type: [u8; 4], id: u32) -> Result<ResourceReader<&mut R>, Error> {
+        let &DRSResource { size, offset, .. } = self.get_resource(resource_type, id)?;
+        Ok(ResourceReader::new(&mut self.handle, u64::from(offset), u64::from(size)))
+    }
+
     pub fn tables(&self) -> DRSTableIterator {
         self.tables.iter()
     }
@@ -257,10 +429,148 @@ impl<R: Read + Seek> DRS<R> {
     }
 }
 
+/// The copyright banner written into the header of archives created with `DRSWriter`.
+const DEFAULT_BANNER_MSG: [u8; 40] = *b"Copyright (c) 1997 Ensemble Studios.\x1a\0\0\0";
+
+/// Size in bytes of the archive header (banner, version, password, table count, directory size).
+const HEADER_SIZE: u32 = 64;
+/// Size in bytes of a single table header entry (resource type, offset, resource count).
+const TABLE_HEADER_SIZE: u32 = 12;
+/// Size in bytes of a single resource entry (id, offset, size).
+const RESOURCE_ENTRY_SIZE: u32 = 12;
+
+/// A resource that has been queued for writing into a DRS archive.
+struct PendingResource {
+    id: u32,
+    data: Vec<u8>,
+}
+
+/// Builds a DRS archive in memory and writes it out to a `Write + Seek` handle.
+pub struct DRSWriter<W: Write + Seek> {
+    handle: W,
+    banner_msg: [u8; 40],
+    password: [u8; 12],
+    tables: Vec<([u8; 4], Vec<PendingResource>)>,
+}
+
+impl<W: Write + Seek> DRSWriter<W> {
+    /// Create a new, empty DRS archive writer for the given handle.
+    pub fn new(handle: W) -> DRSWriter<W> {
+        DRSWriter {
+            handle,
+            banner_msg: DEFAULT_BANNER_MSG,
+            password: [0 as u8; 12],
+            tables: vec![],
+        }
+    }
+
+    /// Create a writer pre-populated with every resource from an existing archive, so it can be
+    /// modified (resources added, replaced) and written back out.
+    pub fn from_drs<R: Read + Seek>(handle: W, drs: &mut DRS<R>) -> Result<DRSWriter<W>, Error> {
+        let mut writer = DRSWriter::new(handle);
+        if let Some(ref header) = drs.header {
+            writer.banner_msg = header.banner_msg;
+            writer.password = header.password;
+        }
+
+        let table_resources: Vec<([u8; 4], Vec<u32>)> = drs.tables()
+            .map(|table| (table.resource_type, table.resources().map(|resource| resource.id).collect()))
+            .collect();
+
+        for (resource_type, ids) in table_resources {
+            for id in ids {
+                let data = drs.read_resource(resource_type, id)?;
+                writer.add_resource(resource_type, id, &data);
+            }
+        }
+
+        Ok(writer)
+    }
+
+    /// Queue a resource to be written to the archive. If a resource with this type and id was
+    /// already added, its data is replaced.
+    pub fn add_resource(&mut self, resource_type: [u8; 4], id: u32, data: &[u8]) {
+        let table_index = match self.tables.iter().position(|(t, _)| *t == resource_type) {
+            Some(index) => index,
+            None => {
+                self.tables.push((resource_type, vec![]));
+                self.tables.len() - 1
+            },
+        };
+        let resources = &mut self.tables[table_index].1;
+
+        match resources.iter_mut().find(|resource| resource.id == id) {
+            Some(resource) => resource.data = data.to_vec(),
+            None => resources.push(PendingResource { id, data: data.to_vec() }),
+        }
+    }
+
+    /// Lay out and write the archive to the underlying handle, consuming the writer.
+    pub fn write(mut self) -> Result<(), Error> {
+        self.tables.sort_by_key(|(resource_type, _)| *resource_type);
+
+        let num_resource_types = self.tables.len() as u32;
+        let table_headers_size = num_resource_types * TABLE_HEADER_SIZE;
+        let entry_tables_size: u32 = self.tables.iter()
+            .map(|(_, resources)| resources.len() as u32 * RESOURCE_ENTRY_SIZE)
+            .sum();
+        let directory_size = HEADER_SIZE + table_headers_size + entry_tables_size;
+
+        // Lay out table offsets (they point into the resource entry tables that follow the table
+        // headers) and resource offsets (they point into the resource data that follows the
+        // directory) before writing anything, so nothing needs to be patched afterwards.
+        let mut table_offset = HEADER_SIZE + table_headers_size;
+        let table_offsets: Vec<u32> = self.tables.iter().map(|(_, resources)| {
+            let offset = table_offset;
+            table_offset += resources.len() as u32 * RESOURCE_ENTRY_SIZE;
+            offset
+        }).collect();
+
+        let mut resource_offset = directory_size;
+        let resource_offsets: Vec<Vec<u32>> = self.tables.iter().map(|(_, resources)| {
+            resources.iter().map(|resource| {
+                let offset = resource_offset;
+                resource_offset += resource.data.len() as u32;
+                offset
+            }).collect()
+        }).collect();
+
+        self.handle.write_all(&self.banner_msg)?;
+        self.handle.write_all(b"1.00")?;
+        self.handle.write_all(&self.password)?;
+        self.handle.write_u32::<LE>(num_resource_types)?;
+        self.handle.write_u32::<LE>(directory_size)?;
+
+        for (i, (resource_type, resources)) in self.tables.iter().enumerate() {
+            self.handle.write_all(resource_type)?;
+            self.handle.write_u32::<LE>(table_offsets[i])?;
+            self.handle.write_u32::<LE>(resources.len() as u32)?;
+        }
+
+        for (t, (_, resources)) in self.tables.iter().enumerate() {
+            for (r, resource) in resources.iter().enumerate() {
+                self.handle.write_u32::<LE>(resource.id)?;
+                self.handle.write_u32::<LE>(resource_offsets[t][r])?;
+                self.handle.write_u32::<LE>(resource.data.len() as u32)?;
+            }
+        }
+
+        for (_, resources) in &self.tables {
+            for resource in resources {
+                self.handle.write_all(&resource.data)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str;
     use std::fs::File;
+    use std::io::Cursor;
+    use byteorder::{WriteBytesExt, LE};
 
     #[test]
     fn it_works() {
@@ -277,4 +587,122 @@ mod tests {
 
         assert!(false);
     }
+
+    /// Append a DRS archive header to `buf`.
+    fn push_header(buf: &mut Vec<u8>, version: &[u8; 4], num_resource_types: u32, directory_size: u32) {
+        buf.extend_from_slice(&[0 as u8; 40]);
+        buf.extend_from_slice(version);
+        buf.extend_from_slice(&[0 as u8; 12]);
+        buf.write_u32::<LE>(num_resource_types).unwrap();
+        buf.write_u32::<LE>(directory_size).unwrap();
+    }
+
+    /// Append a table header to `buf`.
+    fn push_table_header(buf: &mut Vec<u8>, resource_type: &[u8; 4], offset: u32, num_resources: u32) {
+        buf.extend_from_slice(resource_type);
+        buf.write_u32::<LE>(offset).unwrap();
+        buf.write_u32::<LE>(num_resources).unwrap();
+    }
+
+    /// Append a resource entry to `buf`.
+    fn push_resource_entry(buf: &mut Vec<u8>, id: u32, offset: u32, size: u32) {
+        buf.write_u32::<LE>(id).unwrap();
+        buf.write_u32::<LE>(offset).unwrap();
+        buf.write_u32::<LE>(size).unwrap();
+    }
+
+    #[test]
+    fn writer_round_trip() {
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut writer = ::DRSWriter::new(&mut buffer);
+            writer.add_resource(*b" txt", 1, b"hello world");
+            writer.add_resource(*b" wav", 2, b"sound data");
+            writer.write().unwrap();
+        }
+
+        buffer.set_position(0);
+        let mut drs = ::DRS::new(buffer).unwrap();
+
+        assert_eq!(&*drs.read_resource(*b" txt", 1).unwrap(), b"hello world");
+        assert_eq!(&*drs.read_resource(*b" wav", 2).unwrap(), b"sound data");
+        assert_eq!(drs.get_resource_type(1), Some(*b" txt"));
+        assert_eq!(drs.get_resource_type(2), Some(*b" wav"));
+    }
+
+    #[test]
+    fn validate_detects_bad_version() {
+        let mut buf = vec![];
+        push_header(&mut buf, b"0.99", 0, ::HEADER_SIZE);
+
+        let mut drs = ::DRS::new(Cursor::new(buf)).unwrap();
+        let errors = drs.validate().unwrap();
+        assert!(errors.contains(&::ValidationError::BadVersion(*b"0.99")));
+    }
+
+    #[test]
+    fn validate_detects_truncated_file() {
+        let mut buf = vec![];
+        push_header(&mut buf, b"1.00", 0, ::HEADER_SIZE + 100);
+
+        let mut drs = ::DRS::new(Cursor::new(buf)).unwrap();
+        let errors = drs.validate().unwrap();
+        assert!(errors.contains(&::ValidationError::TruncatedFile {
+            expected: u64::from(::HEADER_SIZE + 100),
+            actual: u64::from(::HEADER_SIZE),
+        }));
+    }
+
+    #[test]
+    fn validate_detects_table_out_of_bounds() {
+        let mut buf = vec![];
+        let directory_size = ::HEADER_SIZE + ::TABLE_HEADER_SIZE;
+        push_header(&mut buf, b"1.00", 1, directory_size);
+        // Offset 0 points into the header instead of the entry tables that follow it.
+        push_table_header(&mut buf, b"foo ", 0, 0);
+
+        let mut drs = ::DRS::new(Cursor::new(buf)).unwrap();
+        let errors = drs.validate().unwrap();
+        assert!(errors.contains(&::ValidationError::TableOutOfBounds {
+            resource_type: *b"foo ",
+            offset: 0,
+        }));
+    }
+
+    #[test]
+    fn validate_detects_resource_out_of_bounds() {
+        let mut buf = vec![];
+        let directory_size = ::HEADER_SIZE + ::TABLE_HEADER_SIZE + ::RESOURCE_ENTRY_SIZE;
+        push_header(&mut buf, b"1.00", 1, directory_size);
+        push_table_header(&mut buf, b"foo ", ::HEADER_SIZE + ::TABLE_HEADER_SIZE, 1);
+        push_resource_entry(&mut buf, 1, directory_size, 100);
+        buf.extend_from_slice(b"too short");
+
+        let mut drs = ::DRS::new(Cursor::new(buf)).unwrap();
+        let errors = drs.validate().unwrap();
+        assert!(errors.contains(&::ValidationError::ResourceOutOfBounds {
+            resource_type: *b"foo ",
+            id: 1,
+            offset: directory_size,
+            size: 100,
+        }));
+    }
+
+    #[test]
+    fn validate_detects_overlapping_resources() {
+        let mut buf = vec![];
+        let directory_size = ::HEADER_SIZE + ::TABLE_HEADER_SIZE + ::RESOURCE_ENTRY_SIZE * 2;
+        push_header(&mut buf, b"1.00", 1, directory_size);
+        push_table_header(&mut buf, b"foo ", ::HEADER_SIZE + ::TABLE_HEADER_SIZE, 2);
+        push_resource_entry(&mut buf, 1, directory_size, 20);
+        push_resource_entry(&mut buf, 2, directory_size + 10, 20);
+        buf.extend_from_slice(&[0 as u8; 30]);
+
+        let mut drs = ::DRS::new(Cursor::new(buf)).unwrap();
+        let errors = drs.validate().unwrap();
+        assert!(errors.contains(&::ValidationError::OverlappingResources {
+            a: (*b"foo ", 1),
+            b: (*b"foo ", 2),
+        }));
+    }
 }